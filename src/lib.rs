@@ -1,20 +1,40 @@
 use nalgebra::geometry::Perspective3;
 use nalgebra::geometry::Point3;
 use nalgebra::Vector3;
-use nalgebra::{Isometry3, Matrix4, Orthographic3, Rotation3};
+use nalgebra::{Isometry3, Matrix4, Orthographic3, Rotation3, UnitQuaternion};
 use ncollide3d::procedural;
 use ncollide3d::procedural::TriMesh;
-use rand_distr::Normal;
+use rand_distr::{Distribution, Normal};
 use std::cell::RefCell;
 use std::f64::consts::FRAC_PI_2;
 use std::f64::consts::PI;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::HtmlCanvasElement;
 
-const DOT_COLOR: &str = "rgba(0,0,0,1)";
+mod obj;
+mod render_backend;
+
+use render_backend::{Canvas2dBackend, RenderBackend, WebGlBackend};
+
+const DOT_COLOR_RGB: &str = "0,0,0";
 const TRANSPARENT: &str = "rgba(0,0,0,0)";
+// Reference depth at which a dot is drawn at its configured radius and full opacity.
+const DOT_REFERENCE_DEPTH: f64 = 30.0;
+// Half-height, in world units, of the orthographic view volume. Framed to fit
+// the largest sphere (diameter 60) with a little margin on either side.
+const ORTHO_HALF_HEIGHT: f64 = 40.0;
+
+// Radians of orbit rotation per pixel of pointer drag.
+const DRAG_SENSITIVITY: f64 = 0.005;
+// Per-frame multiplier applied to the inertial spin after the pointer is released.
+const INERTIA_DAMPING: f64 = 0.95;
+// Below this angular speed the inertial spin is considered settled.
+const INERTIA_EPSILON: f64 = 0.0001;
+// Frames of no dragging and no residual inertia before ambient auto-rotation resumes.
+const IDLE_FRAMES_BEFORE_AUTO_ROTATE: u32 = 120;
 
 macro_rules! log {
     ( $( $t:tt )* ) => {
@@ -31,29 +51,213 @@ struct DotSet {
     mesh: TriMesh<f64>,
     radius: f64,
     rotation: [f64; 3],
+    layer_phase: f64,
+    period: f64,
+    amplitude: f64,
+    speed: f64,
+    // Per-vertex offset sampled once from `spread` so the jitter stays put
+    // frame-to-frame instead of reshuffling.
+    jitter: Vec<f64>,
 }
 
 impl DotSet {
-    fn new(config: &SetConfig) -> DotSet {
+    async fn load(config: &SetConfig) -> DotSet {
+        let mesh = match &config.source {
+            MeshSource::Sphere { u, v, diameter } => procedural::sphere(*diameter, *u, *v, false),
+            MeshSource::Obj { url } => fetch_obj_mesh(url).await.unwrap_or_else(|err| {
+                log!("failed to load obj mesh from {}: {:?}", url, err);
+                TriMesh::new(Vec::new(), None, None, None)
+            }),
+        };
+
+        let mut rng = rand::thread_rng();
+        let jitter = (0..mesh.coords.len())
+            .map(|_| config.spread.sample(&mut rng))
+            .collect();
+
         DotSet {
-            mesh: procedural::sphere(config.diameter, config.u, config.v, false),
+            mesh,
             radius: config.radius,
             rotation: config.ro,
+            layer_phase: config.l,
+            period: config.period,
+            amplitude: config.amplitude,
+            speed: config.speed,
+            jitter,
         }
     }
 }
 
+enum MeshSource {
+    Sphere { u: u32, v: u32, diameter: f64 },
+    Obj { url: String },
+}
+
 struct SetConfig {
     l: f64,
-    v: u32,
-    u: u32,
-    diameter: f64,
     radius: f64,
     ro: [f64; 3],
     period: f64,
     amplitude: f64,
     speed: f64,
     spread: Normal<f64>,
+    source: MeshSource,
+}
+
+async fn fetch_obj_mesh(url: &str) -> Result<TriMesh<f64>, JsValue> {
+    let window = web_sys::window().unwrap();
+    let response = JsFuture::from(window.fetch_with_str(url)).await?;
+    let response: web_sys::Response = response.dyn_into()?;
+    let text = JsFuture::from(response.text()?).await?;
+    let text = text
+        .as_string()
+        .ok_or_else(|| JsValue::from_str("obj response body was not text"))?;
+
+    Ok(obj::parse(&text))
+}
+
+struct Camera {
+    use_perspective: bool,
+    width: f64,
+    height: f64,
+    fov: f64,
+    near: f64,
+    far: f64,
+    eye: Point3<f64>,
+    target: Point3<f64>,
+    model: Isometry3<f64>,
+    view_model: Matrix4<f64>,
+    projection: Matrix4<f64>,
+    translation: Vector3<f64>,
+}
+
+impl Camera {
+    fn new(width: f64, height: f64, model: Isometry3<f64>) -> Camera {
+        let mut camera = Camera {
+            use_perspective: false,
+            width,
+            height,
+            fov: 75.0_f64.to_radians(),
+            near: 1.0,
+            far: 100.0,
+            eye: Point3::new(0.0, 0.0, 30.0),
+            target: Point3::new(0.0, 0.0, 0.0),
+            model,
+            view_model: Matrix4::identity(),
+            projection: Matrix4::identity(),
+            translation: nalgebra::zero(),
+        };
+        camera.recompute();
+        camera
+    }
+
+    fn recompute(&mut self) {
+        let aspect_ratio = self.width / self.height;
+        self.projection = if self.use_perspective {
+            Perspective3::new(aspect_ratio, self.fov, self.near, self.far).to_homogeneous()
+        } else {
+            let half_height = ORTHO_HALF_HEIGHT;
+            let half_width = half_height * aspect_ratio;
+            Orthographic3::new(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                self.near,
+                self.far,
+            )
+            .to_homogeneous()
+        };
+
+        let view = Isometry3::look_at_rh(&self.eye, &self.target, &Vector3::y());
+        self.view_model = (view * self.model).to_homogeneous();
+        self.translation = Vector3::new(self.width / 2.0, self.height / 2.0, 0.0);
+    }
+}
+
+/// Pointer-driven orbit: a user rotation composed on top of each set's ambient
+/// spin, plus a decaying angular velocity that keeps the globe spinning after
+/// the pointer is released.
+struct InputState {
+    user_rotation: UnitQuaternion<f64>,
+    angular_velocity: Vector3<f64>,
+    dragging: bool,
+    last_pointer: Option<(f64, f64)>,
+    idle_frames: u32,
+}
+
+impl InputState {
+    fn new() -> InputState {
+        InputState {
+            user_rotation: UnitQuaternion::identity(),
+            angular_velocity: nalgebra::zero(),
+            dragging: false,
+            last_pointer: None,
+            idle_frames: 0,
+        }
+    }
+}
+
+struct AnimationParams {
+    amplitude_scale: f64,
+    speed_scale: f64,
+}
+
+thread_local! {
+    static CAMERA: RefCell<Option<Rc<RefCell<Camera>>>> = RefCell::new(None);
+    static SETS: RefCell<Option<Rc<RefCell<Vec<DotSet>>>>> = RefCell::new(None);
+    static ANIMATION: RefCell<AnimationParams> = RefCell::new(AnimationParams {
+        amplitude_scale: 1.0,
+        speed_scale: 1.0,
+    });
+}
+
+/// Scales every dot set's pulse amplitude and speed. Callable from JS.
+#[wasm_bindgen]
+pub fn set_animation(amplitude_scale: f64, speed_scale: f64) {
+    ANIMATION.with(|cell| {
+        let mut params = cell.borrow_mut();
+        params.amplitude_scale = amplitude_scale;
+        params.speed_scale = speed_scale;
+    });
+}
+
+/// Fetches an OBJ mesh from `url` and adds it to the running scene as a new
+/// dot set, point-cloud-rendered through the same pipeline as the procedural
+/// spheres. Callable from JS once the scene has started.
+#[wasm_bindgen]
+pub fn load_obj_set(url: String, radius: f64) {
+    let sets = match SETS.with(|cell| cell.borrow().clone()) {
+        Some(sets) => sets,
+        None => return,
+    };
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let config = SetConfig {
+            l: 1.0,
+            radius,
+            ro: [0.0, 0.0, 0.0],
+            period: 0.0,
+            amplitude: 0.0,
+            speed: 0.0,
+            spread: Normal::new(0.0, 1.0).unwrap(),
+            source: MeshSource::Obj { url },
+        };
+        let dotset = DotSet::load(&config).await;
+        sets.borrow_mut().push(dotset);
+    });
+}
+
+/// Switches between orthographic and perspective projection. Callable from JS.
+#[wasm_bindgen]
+pub fn set_perspective(enabled: bool) {
+    CAMERA.with(|cell| {
+        if let Some(camera) = cell.borrow().as_ref() {
+            let mut camera = camera.borrow_mut();
+            camera.use_perspective = enabled;
+            camera.recompute();
+        }
+    });
 }
 
 #[wasm_bindgen(start)]
@@ -66,12 +270,19 @@ pub fn start() {
         .map_err(|_| ())
         .unwrap();
 
-    let context = canvas
-        .get_context("2d")
-        .unwrap()
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
+    let mut backend: Box<dyn RenderBackend> = match WebGlBackend::new(&canvas) {
+        Ok(webgl) => Box::new(webgl),
+        Err(_) => {
+            log!("WebGL unavailable, falling back to Canvas2D");
+            let context = canvas
+                .get_context("2d")
+                .unwrap()
+                .unwrap()
+                .dyn_into::<web_sys::CanvasRenderingContext2d>()
+                .unwrap();
+            Box::new(Canvas2dBackend::new(context))
+        }
+    };
 
     let width = window.inner_width().unwrap().as_f64().unwrap();
     let height = window.inner_height().unwrap().as_f64().unwrap();
@@ -85,129 +296,278 @@ pub fn start() {
     };
 
     let model = Isometry3::new(Vector3::x(), nalgebra::zero());
-    // let projection = Perspective3::new(width/height, 75.0_f64.to_radians(), 1.0, 1000.0);
-    let projection = Orthographic3::new(0.0, 0.25, 0.0, 0.25, 1.0, 100.0);
-    let eye = Point3::new(0.0, 0.0, 30.0);
-    let target = Point3::new(0.0, 0.0, 0.0);
-    let view = Isometry3::look_at_rh(&eye, &target, &Vector3::y());
-    let camera = projection.as_matrix() * (view * model).to_homogeneous();
-
-    let sets: Vec<DotSet> = vec![
+    let camera = Rc::new(RefCell::new(Camera::new(width, height, model)));
+    CAMERA.with(|cell| *cell.borrow_mut() = Some(camera.clone()));
+
+    {
+        let camera = camera.clone();
+        let window = window.clone();
+        let on_resize = Closure::wrap(Box::new(move || {
+            let width = window.inner_width().unwrap().as_f64().unwrap();
+            let height = window.inner_height().unwrap().as_f64().unwrap();
+            let mut camera = camera.borrow_mut();
+            camera.width = width;
+            camera.height = height;
+            camera.recompute();
+        }) as Box<dyn FnMut()>);
+        window
+            .add_event_listener_with_callback("resize", on_resize.as_ref().unchecked_ref())
+            .unwrap();
+        on_resize.forget();
+    }
+
+    let configs = vec![
         SetConfig {
             l: 1.0,
-            v: 1 * scale,
-            u: 2 * scale,
-            diameter: 20.0,
             radius: 4.0,
             ro: [-2.0, -1.0, 3.0],
             period: 0.004,
             amplitude: 100.0,
             speed: 1.0,
             spread: Normal::new(0.0, 50.0).unwrap(),
+            source: MeshSource::Sphere {
+                u: 2 * scale,
+                v: 1 * scale,
+                diameter: 20.0,
+            },
         },
         SetConfig {
             l: 2.0,
-            v: 2 * scale,
-            u: 4 * scale,
-            diameter: 40.0,
             radius: 3.0,
             ro: [-1.0, 1.0, 2.0],
             period: 0.001,
             amplitude: 200.0,
             speed: 1.0,
             spread: Normal::new(0.0, 150.0).unwrap(),
+            source: MeshSource::Sphere {
+                u: 4 * scale,
+                v: 2 * scale,
+                diameter: 40.0,
+            },
         },
         SetConfig {
             l: 3.0,
-            v: 4 * scale,
-            u: 8 * scale,
-            diameter: 60.0,
             radius: 2.0,
             ro: [-1.0, 3.0, 1.0],
             period: 0.006,
             amplitude: 150.0,
             speed: 1.0,
             spread: Normal::new(0.0, 300.0).unwrap(),
+            source: MeshSource::Sphere {
+                u: 8 * scale,
+                v: 4 * scale,
+                diameter: 60.0,
+            },
         },
-    ]
-    .iter()
-    .map(|config| DotSet::new(config))
-    .collect();
+    ];
+
+    let sets: Rc<RefCell<Vec<DotSet>>> = Rc::new(RefCell::new(Vec::new()));
+    SETS.with(|cell| *cell.borrow_mut() = Some(sets.clone()));
+
+    let input = Rc::new(RefCell::new(InputState::new()));
+
+    {
+        let input = input.clone();
+        let on_pointer_down = Closure::wrap(Box::new(move |event: web_sys::PointerEvent| {
+            let mut input = input.borrow_mut();
+            input.dragging = true;
+            input.angular_velocity = nalgebra::zero();
+            input.last_pointer = Some((event.client_x() as f64, event.client_y() as f64));
+        }) as Box<dyn FnMut(_)>);
+        canvas
+            .add_event_listener_with_callback("pointerdown", on_pointer_down.as_ref().unchecked_ref())
+            .unwrap();
+        on_pointer_down.forget();
+    }
+
+    // Listen on the window, not the canvas, so a drag keeps tracking the
+    // pointer even if it strays outside the canvas bounds mid-gesture.
+    {
+        let input = input.clone();
+        let on_pointer_move = Closure::wrap(Box::new(move |event: web_sys::PointerEvent| {
+            let mut input = input.borrow_mut();
+            if !input.dragging {
+                return;
+            }
+            let (x, y) = (event.client_x() as f64, event.client_y() as f64);
+            if let Some((last_x, last_y)) = input.last_pointer {
+                let delta = Vector3::new(
+                    (y - last_y) * DRAG_SENSITIVITY,
+                    (x - last_x) * DRAG_SENSITIVITY,
+                    0.0,
+                );
+                input.user_rotation = UnitQuaternion::from_scaled_axis(delta) * input.user_rotation;
+                input.angular_velocity = delta;
+            }
+            input.last_pointer = Some((x, y));
+            input.idle_frames = 0;
+        }) as Box<dyn FnMut(_)>);
+        window
+            .add_event_listener_with_callback("pointermove", on_pointer_move.as_ref().unchecked_ref())
+            .unwrap();
+        on_pointer_move.forget();
+    }
+
+    {
+        let input = input.clone();
+        let on_pointer_up = Closure::wrap(Box::new(move |_event: web_sys::PointerEvent| {
+            let mut input = input.borrow_mut();
+            input.dragging = false;
+            input.last_pointer = None;
+        }) as Box<dyn FnMut(_)>);
+        window
+            .add_event_listener_with_callback("pointerup", on_pointer_up.as_ref().unchecked_ref())
+            .unwrap();
+        window
+            .add_event_listener_with_callback(
+                "pointercancel",
+                on_pointer_up.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        on_pointer_up.forget();
+    }
+
+    for config in configs {
+        let sets = sets.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let dotset = DotSet::load(&config).await;
+            sets.borrow_mut().push(dotset);
+        });
+    }
 
     let render = Rc::new(RefCell::new(None));
     let g = render.clone();
     let mut t = 0.0;
-
-    let translation = Vector3::new(width / 2.0, height / 2.0, 0.0);
+    // Drives the per-vertex pulse/shimmer in `draw_dotset`. Unlike `t`, this
+    // always advances - dragging or the post-drag idle window should only
+    // suppress the ambient orbit, not freeze the surface animation.
+    let mut pulse_t = 0.0;
 
     *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
-        context.clear_rect(0.0, 0.0, width, height);
-        for set in &sets {
+        let camera = camera.borrow();
+
+        let user_rotation_matrix = {
+            let mut input = input.borrow_mut();
+            if !input.dragging && input.angular_velocity.norm() > INERTIA_EPSILON {
+                let step = UnitQuaternion::from_scaled_axis(input.angular_velocity);
+                input.user_rotation = step * input.user_rotation;
+                input.angular_velocity *= INERTIA_DAMPING;
+            }
+            input.idle_frames = if input.dragging || input.angular_velocity.norm() > INERTIA_EPSILON
+            {
+                0
+            } else {
+                input.idle_frames.saturating_add(1)
+            };
+            Rotation3::from(input.user_rotation)
+        };
+        let auto_rotate_active =
+            input.borrow().idle_frames >= IDLE_FRAMES_BEFORE_AUTO_ROTATE;
+
+        backend.begin_frame();
+        backend.clear(camera.width, camera.height);
+        for set in sets.borrow().iter() {
             let rotation_x =
                 Rotation3::from_axis_angle(&Vector3::x_axis(), FRAC_PI_2 * t * set.rotation[0]);
             let rotation_y =
                 Rotation3::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2 * t * set.rotation[1]);
             let rotation_z =
                 Rotation3::from_axis_angle(&Vector3::z_axis(), FRAC_PI_2 * t * set.rotation[2]);
-            let rotation = rotation_x * rotation_y * rotation_z;
-            draw_dotset(&context, &camera, &translation, &rotation, &set, t);
+            let rotation = user_rotation_matrix * rotation_x * rotation_y * rotation_z;
+            draw_dotset(
+                backend.as_mut(),
+                &camera.view_model,
+                &camera.projection,
+                &camera.translation,
+                &rotation,
+                &set,
+                pulse_t,
+            );
         }
+        backend.end_frame();
 
-        t += 0.002;
+        if auto_rotate_active {
+            t += 0.002;
+        }
+        pulse_t += 0.002;
         request_animation_frame(render.borrow().as_ref().unwrap());
     }) as Box<dyn FnMut()>));
 
     request_animation_frame(g.borrow().as_ref().unwrap());
 }
 
-fn draw_dot(context: &CanvasRenderingContext2d, point: &Point3<f64>, radius: f64) {
-    context.begin_path();
-
-    context
-        .arc(point.x, point.y, radius, 0.0, 2.0 * PI)
-        .unwrap();
-
-    let mut gradient = context
-        .create_radial_gradient(point.x, point.y, 0.0, point.x, point.y, radius)
-        .unwrap();
-    gradient.add_color_stop(0.0, DOT_COLOR).unwrap();
-    gradient.add_color_stop(1.0, TRANSPARENT).unwrap();
-
-    context.set_fill_style(&gradient);
-    context.fill();
-
-    context.close_path();
-}
-
-fn draw_line(
-    context: &CanvasRenderingContext2d,
-    start: &Point3<f64>,
-    end: &Point3<f64>,
-    color: &str,
-) {
-    context.begin_path();
-
-    context.set_stroke_style(&JsValue::from_str(color));
-    context.move_to(start.x, start.y);
-    context.line_to(end.x, end.y);
-    context.stroke();
-
-    context.close_path();
-}
-
 fn draw_dotset(
-    context: &CanvasRenderingContext2d,
-    camera: &Matrix4<f64>,
+    backend: &mut dyn RenderBackend,
+    view_model: &Matrix4<f64>,
+    projection: &Matrix4<f64>,
     translation: &Vector3<f64>,
     rotation: &Rotation3<f64>,
     dotset: &DotSet,
     t: f64,
 ) {
-    for point in &dotset.mesh.coords {
-        let point1 = camera.transform_point(&(rotation * point)) + translation;
-        // let point = (rotation * point) + translation;
-        // log!("{:?} {:?}", point, point1);
-        draw_dot(context, &point1, dotset.radius);
+    let (amplitude_scale, speed_scale) = ANIMATION.with(|cell| {
+        let params = cell.borrow();
+        (params.amplitude_scale, params.speed_scale)
+    });
+    let amplitude = dotset.amplitude * amplitude_scale;
+    let speed = dotset.speed * speed_scale;
+    let pulse =
+        amplitude * (2.0 * PI * (dotset.period * dotset.layer_phase) + speed * t).sin();
+
+    let mut depths: Vec<(Point3<f64>, f64)> = dotset
+        .mesh
+        .coords
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            dotset
+                .mesh
+                .normals
+                .as_ref()
+                .map(|normals| {
+                    let world_normal = rotation * normals[*i];
+                    let camera_normal = view_model.transform_vector(&world_normal);
+                    camera_normal.z > 0.0
+                })
+                .unwrap_or(true)
+        })
+        .map(|(i, point)| {
+            let displaced = match dotset.mesh.normals.as_ref() {
+                Some(normals) => point + normals[i] * (pulse + dotset.jitter[i]),
+                None => *point,
+            };
+            let camera_point = view_model.transform_point(&(rotation * displaced));
+            (camera_point, -camera_point.z)
+        })
+        // A malformed source mesh (e.g. a "nan"/"inf" vertex from obj::parse)
+        // can produce a non-finite depth; drop such dots before they are sorted
+        // and projected.
+        .filter(|(_, depth)| depth.is_finite())
+        .collect();
+
+    // Painter's algorithm: draw farthest dots first so nearer ones occlude them.
+    depths.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (camera_point, depth) in depths {
+        // `transform_point` performs the perspective divide and hands back real
+        // NDC coordinates in [-1, 1] (exactly, for `Perspective3`; `Orthographic3`
+        // is tuned to the same range via `ORTHO_HALF_HEIGHT`), so the standard
+        // viewport transform is to scale by the half-canvas extents before
+        // shifting to the canvas center.
+        let ndc = projection.transform_point(&camera_point);
+        let screen_point = Point3::new(
+            ndc.x * translation.x + translation.x,
+            ndc.y * translation.y + translation.y,
+            ndc.z,
+        );
+        let depth_scale = (DOT_REFERENCE_DEPTH / depth).max(0.0);
+        let radius = dotset.radius * depth_scale;
+        let alpha = depth_scale.min(1.0);
+        backend.draw_point(
+            &screen_point,
+            radius,
+            &format!("rgba({},{})", DOT_COLOR_RGB, alpha),
+        );
     }
 }
 
@@ -0,0 +1,87 @@
+//! Minimal Wavefront OBJ parser. Pulls vertex positions and triangulated face
+//! indices into the same `TriMesh` the procedural sphere generator produces,
+//! so an arbitrary model can flow through the existing dot-rendering pipeline.
+
+use nalgebra::Point3;
+use ncollide3d::procedural::{IndexBuffer, TriMesh};
+
+pub fn parse(source: &str) -> TriMesh<f64> {
+    let mut coords = Vec::new();
+    let mut indices = Vec::new();
+
+    for line in source.lines() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let values: Vec<f64> = tokens.filter_map(|token| token.parse().ok()).collect();
+                if let [x, y, z, ..] = values[..] {
+                    coords.push(Point3::new(x, y, z));
+                }
+            }
+            Some("f") => {
+                let face: Vec<u32> = tokens
+                    // OBJ face components can be "v", "v/vt" or "v/vt/vn" - the
+                    // vertex index is always the first field.
+                    .filter_map(|token| token.split('/').next())
+                    .filter_map(|index| index.parse::<u32>().ok())
+                    // OBJ indices are 1-based; a literal "0" is malformed and
+                    // would underflow the subtraction, so drop it instead of
+                    // panicking on untrusted input.
+                    .filter_map(|index| index.checked_sub(1))
+                    .collect();
+
+                // Fan-triangulate faces with more than three vertices.
+                for i in 1..face.len().saturating_sub(1) {
+                    indices.push(Point3::new(face[0], face[i], face[i + 1]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    TriMesh::new(coords, None, None, Some(IndexBuffer::Unified(indices)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unified_indices(mesh: &TriMesh<f64>) -> &[Point3<u32>] {
+        match mesh.indices {
+            IndexBuffer::Unified(ref faces) => faces,
+            IndexBuffer::Split(_) => panic!("expected a unified index buffer"),
+        }
+    }
+
+    #[test]
+    fn parses_a_valid_quad() {
+        let mesh = parse(
+            "v 0.0 0.0 0.0\n\
+             v 1.0 0.0 0.0\n\
+             v 1.0 1.0 0.0\n\
+             v 0.0 1.0 0.0\n\
+             f 1 2 3 4\n",
+        );
+
+        assert_eq!(mesh.coords.len(), 4);
+        assert_eq!(mesh.coords[0], Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(mesh.coords[2], Point3::new(1.0, 1.0, 0.0));
+
+        // The quad should fan-triangulate into two faces sharing vertex 0.
+        assert_eq!(unified_indices(&mesh), &[Point3::new(0, 1, 2), Point3::new(0, 2, 3)]);
+    }
+
+    #[test]
+    fn drops_a_malformed_zero_face_index_instead_of_panicking() {
+        let mesh = parse(
+            "v 0.0 0.0 0.0\n\
+             v 1.0 0.0 0.0\n\
+             v 1.0 1.0 0.0\n\
+             f 0 1 2\n",
+        );
+
+        // "0" is not a valid 1-based OBJ index; it's dropped rather than
+        // underflowing, leaving too few vertices to form a triangle.
+        assert!(unified_indices(&mesh).is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+//! Drawing-surface abstraction so the rotation/projection pipeline in `lib.rs`
+//! doesn't need to know whether dots end up on a 2D canvas or in a WebGL buffer.
+
+use nalgebra::Point3;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, WebGlBuffer, WebGlProgram,
+    WebGlRenderingContext, WebGlShader, WebGlUniformLocation,
+};
+
+use crate::TRANSPARENT;
+
+/// A surface that the dot/line pipeline can render onto. `begin_frame`/`end_frame`
+/// bracket a frame so a backend can batch work (e.g. a single WebGL draw call)
+/// instead of issuing one draw per dot.
+pub trait RenderBackend {
+    fn begin_frame(&mut self);
+    fn clear(&mut self, width: f64, height: f64);
+    fn draw_point(&mut self, pos: &Point3<f64>, radius: f64, color: &str);
+    fn draw_line(&mut self, start: &Point3<f64>, end: &Point3<f64>, color: &str);
+    fn end_frame(&mut self);
+}
+
+/// The original rendering path: one `arc` + radial gradient per dot.
+pub struct Canvas2dBackend {
+    context: CanvasRenderingContext2d,
+}
+
+impl Canvas2dBackend {
+    pub fn new(context: CanvasRenderingContext2d) -> Canvas2dBackend {
+        Canvas2dBackend { context }
+    }
+}
+
+impl RenderBackend for Canvas2dBackend {
+    fn begin_frame(&mut self) {}
+
+    fn clear(&mut self, width: f64, height: f64) {
+        self.context.clear_rect(0.0, 0.0, width, height);
+    }
+
+    fn draw_point(&mut self, pos: &Point3<f64>, radius: f64, color: &str) {
+        if radius <= 0.0 {
+            return;
+        }
+
+        self.context.begin_path();
+
+        self.context
+            .arc(pos.x, pos.y, radius, 0.0, 2.0 * std::f64::consts::PI)
+            .unwrap();
+
+        let mut gradient = self
+            .context
+            .create_radial_gradient(pos.x, pos.y, 0.0, pos.x, pos.y, radius)
+            .unwrap();
+        gradient.add_color_stop(0.0, color).unwrap();
+        gradient.add_color_stop(1.0, TRANSPARENT).unwrap();
+
+        self.context.set_fill_style(&gradient);
+        self.context.fill();
+
+        self.context.close_path();
+    }
+
+    fn draw_line(&mut self, start: &Point3<f64>, end: &Point3<f64>, color: &str) {
+        self.context.begin_path();
+
+        self.context.set_stroke_style(&JsValue::from_str(color));
+        self.context.move_to(start.x, start.y);
+        self.context.line_to(end.x, end.y);
+        self.context.stroke();
+
+        self.context.close_path();
+    }
+
+    fn end_frame(&mut self) {}
+}
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    attribute float a_radius;
+    attribute float a_alpha;
+
+    uniform vec2 u_resolution;
+
+    varying float v_alpha;
+
+    void main() {
+        vec2 clip_space = (a_position / u_resolution) * 2.0 - 1.0;
+        gl_Position = vec4(clip_space.x, -clip_space.y, 0.0, 1.0);
+        gl_PointSize = a_radius * 2.0;
+        v_alpha = a_alpha;
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+
+    varying float v_alpha;
+
+    void main() {
+        vec2 offset = gl_PointCoord - vec2(0.5);
+        float falloff = 1.0 - clamp(length(offset) * 2.0, 0.0, 1.0);
+        float alpha = falloff * v_alpha;
+        if (alpha <= 0.0) {
+            discard;
+        }
+        gl_FragColor = vec4(0.0, 0.0, 0.0, alpha);
+    }
+"#;
+
+/// Uploads every projected dot as a point-sprite vertex buffer and draws the
+/// whole set in one `draw_arrays(POINTS, ...)` call, instead of one canvas
+/// `arc` + gradient per dot.
+pub struct WebGlBackend {
+    gl: WebGlRenderingContext,
+    position_location: u32,
+    radius_location: u32,
+    alpha_location: u32,
+    resolution_location: WebGlUniformLocation,
+    position_buffer: WebGlBuffer,
+    radius_buffer: WebGlBuffer,
+    alpha_buffer: WebGlBuffer,
+    positions: Vec<f32>,
+    radii: Vec<f32>,
+    alphas: Vec<f32>,
+    width: f64,
+    height: f64,
+}
+
+impl WebGlBackend {
+    pub fn new(canvas: &HtmlCanvasElement) -> Result<WebGlBackend, JsValue> {
+        let gl: WebGlRenderingContext = canvas
+            .get_context("webgl")?
+            .ok_or_else(|| JsValue::from_str("webgl context unavailable"))?
+            .dyn_into::<WebGlRenderingContext>()?;
+
+        let vertex_shader = compile_shader(
+            &gl,
+            WebGlRenderingContext::VERTEX_SHADER,
+            VERTEX_SHADER,
+        )?;
+        let fragment_shader = compile_shader(
+            &gl,
+            WebGlRenderingContext::FRAGMENT_SHADER,
+            FRAGMENT_SHADER,
+        )?;
+        let program = link_program(&gl, &vertex_shader, &fragment_shader)?;
+        gl.use_program(Some(&program));
+
+        gl.enable(WebGlRenderingContext::BLEND);
+        gl.blend_func(
+            WebGlRenderingContext::SRC_ALPHA,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+
+        let position_location = gl.get_attrib_location(&program, "a_position") as u32;
+        let radius_location = gl.get_attrib_location(&program, "a_radius") as u32;
+        let alpha_location = gl.get_attrib_location(&program, "a_alpha") as u32;
+        let resolution_location = gl
+            .get_uniform_location(&program, "u_resolution")
+            .ok_or_else(|| JsValue::from_str("missing u_resolution uniform"))?;
+
+        let position_buffer = gl
+            .create_buffer()
+            .ok_or_else(|| JsValue::from_str("failed to create position buffer"))?;
+        let radius_buffer = gl
+            .create_buffer()
+            .ok_or_else(|| JsValue::from_str("failed to create radius buffer"))?;
+        let alpha_buffer = gl
+            .create_buffer()
+            .ok_or_else(|| JsValue::from_str("failed to create alpha buffer"))?;
+
+        Ok(WebGlBackend {
+            gl,
+            position_location,
+            radius_location,
+            alpha_location,
+            resolution_location,
+            position_buffer,
+            radius_buffer,
+            alpha_buffer,
+            positions: Vec::new(),
+            radii: Vec::new(),
+            alphas: Vec::new(),
+            width: 0.0,
+            height: 0.0,
+        })
+    }
+
+    fn upload(&self, buffer: &WebGlBuffer, location: u32, size: i32, data: &[f32]) {
+        self.gl
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(buffer));
+        unsafe {
+            let view = js_sys::Float32Array::view(data);
+            self.gl.buffer_data_with_array_buffer_view(
+                WebGlRenderingContext::ARRAY_BUFFER,
+                &view,
+                WebGlRenderingContext::DYNAMIC_DRAW,
+            );
+        }
+        self.gl.enable_vertex_attrib_array(location);
+        self.gl.vertex_attrib_pointer_with_i32(
+            location,
+            size,
+            WebGlRenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+    }
+}
+
+/// Pulls the trailing alpha channel out of an `"rgba(r,g,b,a)"` string, since
+/// every dot in this scene shares the same base color and only alpha varies.
+fn alpha_from_color(color: &str) -> f32 {
+    color
+        .trim_start_matches("rgba(")
+        .trim_end_matches(')')
+        .rsplit(',')
+        .next()
+        .and_then(|a| a.trim().parse::<f32>().ok())
+        .unwrap_or(1.0)
+}
+
+impl RenderBackend for WebGlBackend {
+    fn begin_frame(&mut self) {
+        self.positions.clear();
+        self.radii.clear();
+        self.alphas.clear();
+    }
+
+    fn clear(&mut self, width: f64, height: f64) {
+        self.width = width;
+        self.height = height;
+        self.gl
+            .viewport(0, 0, width as i32, height as i32);
+        self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+        self.gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+    }
+
+    fn draw_point(&mut self, pos: &Point3<f64>, radius: f64, color: &str) {
+        if radius <= 0.0 {
+            return;
+        }
+
+        self.positions.push(pos.x as f32);
+        self.positions.push(pos.y as f32);
+        self.radii.push(radius as f32);
+        self.alphas.push(alpha_from_color(color));
+    }
+
+    fn draw_line(&mut self, _start: &Point3<f64>, _end: &Point3<f64>, _color: &str) {
+        // The WebGL backend only batches point sprites today; lines are a
+        // no-op here until the globe needs wireframe edges rendered in GL.
+    }
+
+    fn end_frame(&mut self) {
+        if self.positions.is_empty() {
+            return;
+        }
+
+        self.gl.uniform2f(
+            Some(&self.resolution_location),
+            self.width as f32,
+            self.height as f32,
+        );
+
+        let point_count = self.radii.len() as i32;
+        self.upload(&self.position_buffer, self.position_location, 2, &self.positions);
+        self.upload(&self.radius_buffer, self.radius_location, 1, &self.radii);
+        self.upload(&self.alpha_buffer, self.alpha_location, 1, &self.alphas);
+
+        self.gl
+            .draw_arrays(WebGlRenderingContext::POINTS, 0, point_count);
+    }
+}
+
+fn compile_shader(
+    gl: &WebGlRenderingContext,
+    shader_type: u32,
+    source: &str,
+) -> Result<WebGlShader, JsValue> {
+    let shader = gl
+        .create_shader(shader_type)
+        .ok_or_else(|| JsValue::from_str("unable to create shader"))?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+
+    if gl
+        .get_shader_parameter(&shader, WebGlRenderingContext::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(shader)
+    } else {
+        Err(JsValue::from_str(
+            &gl.get_shader_info_log(&shader)
+                .unwrap_or_else(|| "unknown shader error".into()),
+        ))
+    }
+}
+
+fn link_program(
+    gl: &WebGlRenderingContext,
+    vertex_shader: &WebGlShader,
+    fragment_shader: &WebGlShader,
+) -> Result<WebGlProgram, JsValue> {
+    let program = gl
+        .create_program()
+        .ok_or_else(|| JsValue::from_str("unable to create program"))?;
+    gl.attach_shader(&program, vertex_shader);
+    gl.attach_shader(&program, fragment_shader);
+    gl.link_program(&program);
+
+    if gl
+        .get_program_parameter(&program, WebGlRenderingContext::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(program)
+    } else {
+        Err(JsValue::from_str(
+            &gl.get_program_info_log(&program)
+                .unwrap_or_else(|| "unknown program error".into()),
+        ))
+    }
+}